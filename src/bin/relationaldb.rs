@@ -1,12 +1,14 @@
 #![warn(clippy::all)]
 
+use std::path::Path;
+
 use clap::{app_from_crate, crate_authors, crate_description, crate_name, crate_version};
-use relationaldb_rs::{error::Result, server::Server};
+use relationaldb_rs::{config::Config, error::Result, server::Server};
 
 /// Service program entry
 #[tokio::main]
 async fn main() -> Result<()> {
-    let _opts = app_from_crate!()
+    let opts = app_from_crate!()
         .arg(
             clap::Arg::with_name("config")
                 .short("c")
@@ -17,5 +19,7 @@ async fn main() -> Result<()> {
         )
         .get_matches();
 
-    Server::new().await?.listen().await?.serve().await
+    let config = Config::load(Path::new(opts.value_of("config").unwrap()))?;
+
+    Server::new(&config).await?.listen().await?.serve().await
 }
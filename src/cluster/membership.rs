@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::cluster::HashRing;
+
+/// A known cluster peer and when it was last heard from over `Ping`.
+#[derive(Debug, Clone)]
+pub struct Peer {
+    pub addr: SocketAddr,
+    pub last_heartbeat: Option<Instant>,
+}
+
+/// The set of peers this node knows about, seeded from the config file and
+/// refreshed by `Ping` heartbeats over the node-to-node RPC.
+pub struct Membership {
+    peers: Mutex<HashMap<String, Peer>>,
+    /// Rebuilt whenever a brand-new node is learned, so a node discovered
+    /// after boot can become a shard owner/replica target too.
+    ring: Mutex<Arc<HashRing>>,
+}
+
+impl Membership {
+    pub fn new(seeds: &[SocketAddr]) -> Self {
+        let peers: HashMap<String, Peer> = seeds
+            .iter()
+            .map(|addr| (addr.to_string(), Peer { addr: *addr, last_heartbeat: None }))
+            .collect();
+        let ring = Arc::new(HashRing::new(&peers.keys().cloned().collect::<Vec<_>>()));
+        Self { peers: Mutex::new(peers), ring: Mutex::new(ring) }
+    }
+
+    /// Record a heartbeat from `node_id`, learning about it if it's new.
+    pub fn record_heartbeat(&self, node_id: &str, addr: SocketAddr) {
+        let mut peers = self.peers.lock().unwrap();
+        let is_new_node = !peers.contains_key(node_id);
+        peers
+            .entry(node_id.to_string())
+            .or_insert(Peer { addr, last_heartbeat: None })
+            .last_heartbeat = Some(Instant::now());
+
+        if is_new_node {
+            let node_ids: Vec<String> = peers.keys().cloned().collect();
+            drop(peers);
+            *self.ring.lock().unwrap() = Arc::new(HashRing::new(&node_ids));
+        }
+    }
+
+    pub fn node_ids(&self) -> Vec<String> {
+        self.peers.lock().unwrap().keys().cloned().collect()
+    }
+
+    pub fn addr_of(&self, node_id: &str) -> Option<SocketAddr> {
+        self.peers.lock().unwrap().get(node_id).map(|peer| peer.addr)
+    }
+
+    /// The consistent-hash ring over currently known nodes.
+    pub fn ring(&self) -> Arc<HashRing> {
+        Arc::clone(&self.ring.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn heartbeat_from_new_node_rebuilds_the_ring() {
+        let membership = Membership::new(&[]);
+        assert!(membership.ring().owner(0).is_none());
+
+        membership.record_heartbeat("node-a", "127.0.0.1:9000".parse().unwrap());
+        assert_eq!(membership.ring().owner(0), Some("node-a".to_string()));
+    }
+
+    #[test]
+    fn heartbeat_from_known_node_does_not_rebuild_the_ring() {
+        let membership = Membership::new(&["127.0.0.1:9000".parse().unwrap()]);
+        let ring_before = membership.ring();
+
+        membership.record_heartbeat("127.0.0.1:9000", "127.0.0.1:9000".parse().unwrap());
+
+        assert!(Arc::ptr_eq(&ring_before, &membership.ring()));
+    }
+}
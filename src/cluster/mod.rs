@@ -0,0 +1,7 @@
+mod membership;
+mod ring;
+mod replicator;
+
+pub use membership::{Membership, Peer};
+pub use replicator::{ReplicationClass, Replicator};
+pub use ring::HashRing;
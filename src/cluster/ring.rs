@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, HashSet};
+
+/// Virtual nodes per peer, so adding or removing one node only reshuffles a
+/// small, evenly spread slice of pages rather than a contiguous range.
+const VIRTUAL_NODES: usize = 128;
+
+/// Consistent-hash ring routing a `page_id` to the node(s) that own it.
+pub struct HashRing {
+    ring: BTreeMap<u64, String>,
+}
+
+impl HashRing {
+    pub fn new(node_ids: &[String]) -> Self {
+        let mut ring = BTreeMap::new();
+        for node_id in node_ids {
+            for vnode in 0..VIRTUAL_NODES {
+                let key = format!("{node_id}#{vnode}");
+                ring.insert(hash_key(key.as_bytes()), node_id.clone());
+            }
+        }
+        Self { ring }
+    }
+
+    /// The single node that owns `page_id`.
+    pub fn owner(&self, page_id: u32) -> Option<String> {
+        self.walk(page_id).next().map(|(_, node_id)| node_id.clone())
+    }
+
+    /// The `n` distinct nodes holding replicas of `page_id`, walking the
+    /// ring clockwise from its hash and wrapping once at the end.
+    pub fn replicas(&self, page_id: u32, n: usize) -> Vec<String> {
+        if self.ring.is_empty() || n == 0 {
+            return Vec::new();
+        }
+
+        let mut seen = HashSet::new();
+        let mut out = Vec::new();
+        for (_, node_id) in self.walk(page_id) {
+            if seen.insert(node_id.clone()) {
+                out.push(node_id.clone());
+                if out.len() == n {
+                    break;
+                }
+            }
+        }
+        out
+    }
+
+    fn walk(&self, page_id: u32) -> impl Iterator<Item = (&u64, &String)> {
+        let hash = hash_key(&page_id.to_le_bytes());
+        self.ring.range(hash..).chain(self.ring.iter())
+    }
+}
+
+fn hash_key(bytes: &[u8]) -> u64 {
+    let hash = blake3::hash(bytes);
+    u64::from_le_bytes(hash.as_bytes()[..8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod test {
+    use super::HashRing;
+
+    #[test]
+    fn owner_is_stable_for_the_same_page() {
+        let ring = HashRing::new(&["a".to_string(), "b".to_string(), "c".to_string()]);
+        let first = ring.owner(42);
+        assert!(first.is_some());
+        assert_eq!(first, ring.owner(42));
+    }
+
+    #[test]
+    fn replicas_returns_up_to_n_distinct_nodes() {
+        let ring = HashRing::new(&["a".to_string(), "b".to_string(), "c".to_string()]);
+        let replicas = ring.replicas(7, 2);
+        assert_eq!(replicas.len(), 2);
+        assert_ne!(replicas[0], replicas[1]);
+    }
+
+    #[test]
+    fn empty_ring_owns_nothing() {
+        let ring = HashRing::new(&[]);
+        assert_eq!(ring.owner(1), None);
+        assert!(ring.replicas(1, 2).is_empty());
+    }
+}
@@ -0,0 +1,97 @@
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use serde_derive::Deserialize;
+
+use crate::error::{Error, Result};
+
+/// On-disk server/cluster configuration, loaded from the YAML file the CLI
+/// accepts via `--config`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    /// Peers to seed cluster membership with on startup.
+    #[serde(default)]
+    pub peers: Vec<SocketAddr>,
+    /// Number of nodes each sharded page is replicated to.
+    #[serde(default = "default_replication_factor")]
+    pub replication_factor: usize,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    /// Port the Prometheus text-exposition admin endpoint listens on; unset
+    /// disables it.
+    #[serde(default)]
+    pub admin_port: Option<u16>,
+    /// Number of independent buffer pool instances to shard pages across;
+    /// each gets its own queue, clock replacer and lock. The server still
+    /// reaches all of them through one shared `Mutex<BufferPoolManager>`.
+    #[serde(default = "default_buffer_pool_instances")]
+    pub buffer_pool_instances: usize,
+    /// Total number of pages cached across all buffer pool instances
+    /// combined.
+    #[serde(default = "default_buffer_pool_size")]
+    pub buffer_pool_size: usize,
+    /// Directory the node's page file and write-ahead log live in.
+    #[serde(default = "default_data_dir")]
+    pub data_dir: PathBuf,
+    /// This node's identity in cluster membership and the hash ring; set
+    /// explicitly in production so a restart doesn't change which pages
+    /// this node owns.
+    #[serde(default = "default_node_id")]
+    pub node_id: String,
+    /// Address the SQL listener binds to. Also the address this node
+    /// advertises to peers in `Ping`, so it must be reachable from them, not
+    /// just local — loopback only works for single-host development.
+    #[serde(default = "default_bind_addr")]
+    pub bind_addr: SocketAddr,
+}
+
+fn default_replication_factor() -> usize {
+    1
+}
+
+fn default_buffer_pool_instances() -> usize {
+    1
+}
+
+fn default_buffer_pool_size() -> usize {
+    64
+}
+
+fn default_data_dir() -> PathBuf {
+    PathBuf::from("data")
+}
+
+fn default_node_id() -> String {
+    format!("node-{}", std::process::id())
+}
+
+fn default_bind_addr() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 9601))
+}
+
+/// TLS listener settings. Plaintext remains the default so local
+/// development needs no setup; setting `enabled: true` turns on rustls.
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct TlsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// PEM certificate chain for this node.
+    pub cert_path: Option<PathBuf>,
+    /// PEM private key matching `cert_path`.
+    pub key_path: Option<PathBuf>,
+    /// PEM CA bundle used to verify client certificates.
+    pub ca_path: Option<PathBuf>,
+    /// Require and verify a client certificate for mutual auth.
+    #[serde(default)]
+    pub require_client_auth: bool,
+}
+
+impl Config {
+    /// A missing config file just means "single node, no peers" rather than
+    /// an error, so a bare `relationaldb` with no setup still starts.
+    pub fn load(path: &Path) -> Result<Config> {
+        let data = if path.exists() { fs::read_to_string(path)? } else { "{}".to_string() };
+        serde_yaml::from_str(&data).map_err(|err| Error::Internal(err.to_string()))
+    }
+}
@@ -1,18 +1,54 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::cluster::{Membership, ReplicationClass, Replicator};
+use crate::config::Config;
 use crate::error::{Error, Result};
+use crate::storage::buffer_pool_manager::{BufferPoolManager, PoolManager};
+use crate::storage::disk_manager::{DiskManager, PageDevice};
+use crate::storage::metrics::{MetricsSnapshot, METRICS};
+use crate::storage::overflow::{ChunkHash, ChunkStore};
+use crate::storage::{table_page_bound, PAGE_SIZE};
 
 use ::log::{error, info};
 use futures::sink::SinkExt as _;
 use serde_derive::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader as AsyncBufReader};
 use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::rustls::{self, Certificate, PrivateKey};
+use tokio_rustls::TlsAcceptor;
 use tokio_stream::wrappers::TcpListenerStream;
 use tokio_stream::StreamExt as _;
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
 pub struct Server {
     listener: Option<TcpListener>,
+    membership: Arc<Membership>,
+    tls_acceptor: Option<TlsAcceptor>,
+    admin_addr: Option<SocketAddr>,
+    disk_manager: Arc<dyn PageDevice>,
+    replicator: Arc<Replicator>,
+    chunk_store: Arc<ChunkStore>,
+    /// Sharded page cache sitting in front of `disk_manager`; checkpointed
+    /// on a fixed interval by `checkpoint_loop`.
+    storage: Arc<Mutex<BufferPoolManager>>,
+    node_id: String,
+    /// Address the SQL listener binds to and that this node advertises to
+    /// peers in `Ping`, so they have somewhere dialable to reach it back.
+    bind_addr: SocketAddr,
 }
 
-pub struct Session {}
+pub struct Session {
+    membership: Arc<Membership>,
+    /// Certificate common name of the authenticated client, when serving
+    /// over mutual TLS.
+    peer_identity: Option<String>,
+    disk_manager: Arc<dyn PageDevice>,
+    chunk_store: Arc<ChunkStore>,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Request {
@@ -20,20 +56,65 @@ pub enum Request {
     GetTable(String),
     ListTables,
     Status,
+    /// Cluster membership heartbeat. `addr` is the sender's own SQL listen
+    /// address, not the socket's source address — a ping travels from an
+    /// ephemeral outbound port, which isn't something a peer could dial
+    /// back to reach the sender.
+    Ping { node_id: String, addr: SocketAddr },
+    /// Replicate a page's bytes to this node.
+    ReplicatePage { page_id: u32, data: Vec<u8> },
+    /// Fetch a page's current bytes from this node.
+    FetchPage { page_id: u32 },
+    /// Store a value too large for one page, content-chunked and
+    /// deduplicated; the returned handles are what `GetOverflowValue` takes.
+    PutOverflowValue { data: Vec<u8> },
+    /// Reassemble a value previously stored with `PutOverflowValue`.
+    GetOverflowValue { hashes: Vec<ChunkHash> },
 }
 /// A server response.
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Response {
     ListTables(Vec<String>),
+    Pong,
+    Ack,
+    PageData(Vec<u8>),
+    Status(MetricsSnapshot),
+    OverflowHandles(Vec<ChunkHash>),
+    OverflowValue(Vec<u8>),
 }
 
 impl Server {
-    pub async fn new() -> Result<Self> {
-        Ok(Server { listener: None })
+    pub async fn new(config: &Config) -> Result<Self> {
+        let membership = Arc::new(Membership::new(&config.peers));
+        let tls_acceptor = build_tls_acceptor(config)?;
+        let admin_addr = config
+            .admin_port
+            .map(|port| SocketAddr::from(([127, 0, 0, 1], port)));
+        let disk_manager: Arc<dyn PageDevice> = Arc::new(DiskManager::new(&config.data_dir)?);
+        let replicator = Arc::new(Replicator::new(Arc::clone(&membership), config.replication_factor));
+        let chunk_store = Arc::new(ChunkStore::new(Arc::clone(&disk_manager), &config.data_dir)?);
+        let storage = Arc::new(Mutex::new(BufferPoolManager::with_instances(
+            config.buffer_pool_size,
+            Arc::clone(&disk_manager),
+            config.buffer_pool_instances,
+            Some(Arc::clone(&replicator)),
+        )?));
+        Ok(Server {
+            listener: None,
+            membership,
+            tls_acceptor,
+            admin_addr,
+            disk_manager,
+            replicator,
+            chunk_store,
+            storage,
+            node_id: config.node_id.clone(),
+            bind_addr: config.bind_addr,
+        })
     }
 
     pub async fn listen(mut self) -> Result<Self> {
-        let (listener,) = tokio::try_join!(TcpListener::bind("127.0.0.1:9601"),)?;
+        let (listener,) = tokio::try_join!(TcpListener::bind(self.bind_addr),)?;
         self.listener = Some(listener);
         Ok(self)
     }
@@ -43,18 +124,102 @@ impl Server {
             .listener
             .ok_or_else(|| Error::Internal("Must listen before serving".to_string()))?;
 
-        tokio::try_join!(Self::serve_sql(listener),)?;
+        tokio::try_join!(
+            Self::serve_sql(
+                listener,
+                Arc::clone(&self.membership),
+                self.tls_acceptor.clone(),
+                Arc::clone(&self.disk_manager),
+                Arc::clone(&self.chunk_store),
+            ),
+            Self::heartbeat_loop(Arc::clone(&self.membership), self.node_id.clone(), self.bind_addr),
+            Self::serve_admin(self.admin_addr),
+            Self::replication_loop(
+                Arc::clone(&self.disk_manager),
+                Arc::clone(&self.membership),
+                Arc::clone(&self.replicator),
+                self.node_id.clone(),
+            ),
+            Self::checkpoint_loop(Arc::clone(&self.storage)),
+        )?;
+        Ok(())
+    }
+
+    /// Serve the Prometheus text-exposition counters on `admin_addr`; a
+    /// no-op when no admin port is configured.
+    async fn serve_admin(admin_addr: Option<SocketAddr>) -> Result<()> {
+        let Some(admin_addr) = admin_addr else {
+            return Ok(());
+        };
+
+        let listener = TcpListener::bind(admin_addr).await?;
+        let mut listener = TcpListenerStream::new(listener);
+        while let Some(socket) = listener.try_next().await? {
+            tokio::spawn(async move {
+                if let Err(err) = Self::respond_with_metrics(socket).await {
+                    error!("admin metrics write failed: {}", err);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Drain the HTTP request line and headers (discarding them, since the
+    /// only route served is the metrics scrape) and reply with a minimal
+    /// `200 OK` response so a real HTTP client/Prometheus can parse it,
+    /// instead of a bare byte stream.
+    async fn respond_with_metrics(socket: TcpStream) -> Result<()> {
+        let mut lines = AsyncBufReader::new(socket).lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.is_empty() {
+                break;
+            }
+        }
+
+        let body = METRICS.render_prometheus();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body,
+        );
+        lines.get_mut().write_all(response.as_bytes()).await?;
         Ok(())
     }
 
-    async fn serve_sql(listener: TcpListener) -> Result<()> {
+    async fn serve_sql(
+        listener: TcpListener,
+        membership: Arc<Membership>,
+        tls_acceptor: Option<TlsAcceptor>,
+        disk_manager: Arc<dyn PageDevice>,
+        chunk_store: Arc<ChunkStore>,
+    ) -> Result<()> {
         let mut listener = TcpListenerStream::new(listener);
         while let Some(socket) = listener.try_next().await? {
             let peer = socket.peer_addr()?;
-            let session = Session::new()?;
+            let membership = Arc::clone(&membership);
+            let tls_acceptor = tls_acceptor.clone();
+            let disk_manager = Arc::clone(&disk_manager);
+            let chunk_store = Arc::clone(&chunk_store);
             tokio::spawn(async move {
                 info!("Client {} connected!", peer);
-                match session.handle(socket).await {
+                let result = match tls_acceptor {
+                    Some(acceptor) => match acceptor.accept(socket).await {
+                        Ok(tls_stream) => {
+                            let peer_identity = peer_common_name(&tls_stream);
+                            Session::new(membership, peer_identity, disk_manager, chunk_store)
+                                .handle(tls_stream, peer)
+                                .await
+                        }
+                        Err(err) => Err(Error::from(err)),
+                    },
+                    None => {
+                        Session::new(membership, None, disk_manager, chunk_store)
+                            .handle(socket, peer)
+                            .await
+                    }
+                };
+
+                match result {
                     Ok(()) => info!("Client {} disconnected", peer),
                     Err(err) => error!("Client {} error: {}", peer, err),
                 }
@@ -63,20 +228,125 @@ impl Server {
 
         Ok(())
     }
+
+    /// Ping every known peer on a fixed interval so membership reflects
+    /// who's actually reachable, not just who was in the config file.
+    async fn heartbeat_loop(membership: Arc<Membership>, node_id: String, own_addr: SocketAddr) -> Result<()> {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+        loop {
+            interval.tick().await;
+            for peer_id in membership.node_ids() {
+                if let Some(addr) = membership.addr_of(&peer_id) {
+                    if let Err(err) = Self::send_ping(addr, &node_id, own_addr).await {
+                        error!("heartbeat to {} failed: {}", addr, err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Periodically push every page this node owns, per the current hash
+    /// ring, out to its replicas — the way pages written before a peer
+    /// joined still converge once that peer is known to membership,
+    /// instead of replication only ever happening at write time.
+    async fn replication_loop(
+        disk_manager: Arc<dyn PageDevice>,
+        membership: Arc<Membership>,
+        replicator: Arc<Replicator>,
+        node_id: String,
+    ) -> Result<()> {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(30));
+        loop {
+            interval.tick().await;
+
+            // Clamped to the table-page range: `allocated_pages()` is
+            // file-length-derived, so once an overflow chunk has been
+            // written out past `OVERFLOW_PAGE_BASE` it no longer means "how
+            // many table pages exist" — scanning the raw value here would
+            // walk billions of empty page ids, hashing each one against the
+            // ring, and never yield back to the other futures sharing this
+            // task.
+            let allocated = match disk_manager.allocated_pages() {
+                Ok(allocated) => table_page_bound(allocated),
+                Err(err) => {
+                    error!("replication scan failed to read page count: {}", err);
+                    continue;
+                }
+            };
+
+            let ring = membership.ring();
+            for page_id in 0..allocated {
+                if ring.owner(page_id).as_deref() != Some(node_id.as_str()) {
+                    continue;
+                }
+
+                let mut data = vec![0u8; PAGE_SIZE];
+                if disk_manager.read_page(page_id, &mut data).is_err() {
+                    continue;
+                }
+                if let Err(err) = replicator.replicate(page_id, &data, ReplicationClass::Sharded) {
+                    error!("replication of page {} failed: {}", page_id, err);
+                }
+            }
+        }
+    }
+
+    /// Flush every buffer pool instance on a fixed interval, so each one's
+    /// WAL checkpoint advances and a restart never has to replay more than
+    /// this interval's worth of the log.
+    async fn checkpoint_loop(storage: Arc<Mutex<BufferPoolManager>>) -> Result<()> {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            // flush_all_page() does synchronous fsyncs/writes per instance;
+            // run it the same way request handling does its blocking work,
+            // so it doesn't stall the other futures sharing this task.
+            tokio::task::block_in_place(|| storage.lock().unwrap().flush_all_page());
+        }
+    }
+
+    /// Ping the peer at `addr`, identifying this node by `node_id` and
+    /// `own_addr` (this node's own listen address) so the peer learns who
+    /// actually sent the heartbeat and where to reach it back — `addr` here
+    /// is the *target's* address, not the sender's.
+    async fn send_ping(addr: std::net::SocketAddr, node_id: &str, own_addr: SocketAddr) -> Result<()> {
+        let socket = TcpStream::connect(addr).await?;
+        let mut stream = tokio_serde::Framed::new(
+            Framed::new(socket, LengthDelimitedCodec::new()),
+            tokio_serde::formats::Bincode::default(),
+        );
+        stream
+            .send(Request::Ping { node_id: node_id.to_string(), addr: own_addr })
+            .await?;
+        stream.try_next().await?;
+        Ok(())
+    }
 }
 
 impl Session {
-    fn new() -> Result<Self> {
-        Ok(Session {})
+    fn new(
+        membership: Arc<Membership>,
+        peer_identity: Option<String>,
+        disk_manager: Arc<dyn PageDevice>,
+        chunk_store: Arc<ChunkStore>,
+    ) -> Self {
+        Session { membership, peer_identity, disk_manager, chunk_store }
     }
 
-    async fn handle(mut self, socket: TcpStream) -> Result<()> {
+    async fn handle<T>(mut self, socket: T, peer_addr: SocketAddr) -> Result<()>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        if let Some(identity) = &self.peer_identity {
+            info!("Client {} authenticated as {}", peer_addr, identity);
+        }
+
         let mut stream = tokio_serde::Framed::new(
             Framed::new(socket, LengthDelimitedCodec::new()),
             tokio_serde::formats::Bincode::default(),
         );
         while let Some(request) = stream.try_next().await? {
-            let response = tokio::task::block_in_place(|| self.request(request));
+            let response = tokio::task::block_in_place(|| self.request(request, peer_addr));
             let rows: Box<dyn Iterator<Item = Result<Response>> + Send> =
                 Box::new(std::iter::empty());
             stream.send(response).await?;
@@ -85,7 +355,119 @@ impl Session {
         Ok(())
     }
 
-    pub fn request(&mut self, _request: Request) -> Result<Response> {
-        todo!()
+    pub fn request(&mut self, request: Request, peer_addr: std::net::SocketAddr) -> Result<Response> {
+        match request {
+            Request::Ping { node_id, addr } => {
+                self.membership.record_heartbeat(&node_id, addr);
+                Ok(Response::Pong)
+            }
+            Request::ReplicatePage { page_id, data } => {
+                // Logged through the WAL before the page itself is written,
+                // so a torn write on this end is still recoverable by
+                // replay — this intentionally does not route through
+                // `storage`'s buffer pool cache: `FetchPage` reads straight
+                // from `disk_manager` too, so the two stay consistent with
+                // each other without needing to invalidate a cached copy
+                // here.
+                self.disk_manager.write_logged_page(page_id, &data)?;
+                info!("replicated page {} from {}", page_id, peer_addr);
+                Ok(Response::Ack)
+            }
+            Request::FetchPage { page_id } => {
+                let mut data = vec![0u8; PAGE_SIZE];
+                let read = self.disk_manager.read_page(page_id, &mut data)?;
+                data.truncate(read);
+                Ok(Response::PageData(data))
+            }
+            Request::Status => Ok(Response::Status(METRICS.snapshot())),
+            Request::PutOverflowValue { data } => {
+                Ok(Response::OverflowHandles(self.chunk_store.put_value(&data)?))
+            }
+            Request::GetOverflowValue { hashes } => {
+                Ok(Response::OverflowValue(self.chunk_store.get_value(&hashes)?))
+            }
+            Request::Execute(_) | Request::GetTable(_) | Request::ListTables => todo!(),
+        }
     }
 }
+
+/// Build a TLS acceptor from `config.tls`, or `None` to keep serving
+/// plaintext when TLS isn't configured.
+fn build_tls_acceptor(config: &Config) -> Result<Option<TlsAcceptor>> {
+    if !config.tls.enabled {
+        return Ok(None);
+    }
+
+    let cert_path = config
+        .tls
+        .cert_path
+        .as_ref()
+        .ok_or_else(|| Error::Internal("tls.cert_path is required when tls.enabled is true".to_string()))?;
+    let key_path = config
+        .tls
+        .key_path
+        .as_ref()
+        .ok_or_else(|| Error::Internal("tls.key_path is required when tls.enabled is true".to_string()))?;
+
+    let cert_chain = load_certs(cert_path)?;
+    let private_key = load_private_key(key_path)?;
+    let builder = rustls::ServerConfig::builder().with_safe_defaults();
+
+    let server_config = if config.tls.require_client_auth {
+        let ca_path = config
+            .tls
+            .ca_path
+            .as_ref()
+            .ok_or_else(|| Error::Internal("tls.ca_path is required when tls.require_client_auth is true".to_string()))?;
+
+        let mut roots = rustls::RootCertStore::empty();
+        for ca_cert in load_certs(ca_path)? {
+            roots
+                .add(&ca_cert)
+                .map_err(|err| Error::Internal(err.to_string()))?;
+        }
+        let verifier = rustls::server::AllowAnyAuthenticatedClient::new(roots);
+
+        builder
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(cert_chain, private_key)
+    } else {
+        builder
+            .with_no_client_auth()
+            .with_single_cert(cert_chain, private_key)
+    }
+    .map_err(|err| Error::Internal(err.to_string()))?;
+
+    Ok(Some(TlsAcceptor::from(Arc::new(server_config))))
+}
+
+fn load_certs(path: &Path) -> Result<Vec<Certificate>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let der_certs =
+        rustls_pemfile::certs(&mut reader).map_err(|err| Error::Internal(err.to_string()))?;
+    Ok(der_certs.into_iter().map(Certificate).collect())
+}
+
+fn load_private_key(path: &Path) -> Result<PrivateKey> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut keys = rustls_pemfile::pkcs8_private_keys(&mut reader)
+        .map_err(|err| Error::Internal(err.to_string()))?;
+    let key = keys
+        .pop()
+        .ok_or_else(|| Error::Internal(format!("no private key found in {}", path.display())))?;
+    Ok(PrivateKey(key))
+}
+
+/// Pull the certificate common name out of the client cert presented during
+/// the handshake, if mutual auth is on and a client cert was sent.
+fn peer_common_name(stream: &tokio_rustls::server::TlsStream<TcpStream>) -> Option<String> {
+    let (_, connection) = stream.get_ref();
+    let cert = connection.peer_certificates()?.first()?;
+    let (_, parsed) = x509_parser::parse_x509_certificate(&cert.0).ok()?;
+    parsed
+        .subject()
+        .iter_common_name()
+        .next()
+        .and_then(|cn| cn.as_str().ok())
+        .map(|cn| cn.to_string())
+}
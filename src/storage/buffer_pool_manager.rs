@@ -1,26 +1,22 @@
 use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
-use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::cluster::{ReplicationClass, Replicator};
+use crate::error::Result;
 use crate::storage::clock_replacer::ClockReplacer;
-use crate::storage::disk_manager::DiskManager;
-use crate::storage::PAGE_SIZE;
+use crate::storage::disk_manager::PageDevice;
+use crate::storage::metrics::METRICS;
+use crate::storage::wal::LogRecord;
+use crate::storage::{table_page_bound, PAGE_SIZE};
 
 struct BufferPoolCache {
     cache: [u8; PAGE_SIZE],
-    disk_manager: Arc<dyn DiskManager>,
+    disk_manager: Arc<dyn PageDevice>,
     page_id: usize,
-    modified: bool
-}
-
-pub struct BufferPoolManager {
-    queue: VecDeque<(usize, Arc<Mutex<BufferPoolCache>>)>,
-    disk_manager: Arc<dyn DiskManager>,
-    pool_size: usize,
-    /// [summary](https://github.com/cmu-db/bustub/blob/6f4e74f6eb5f56a13bac9b9b9bbc3b2a80b41258/src/include/buffer/buffer_pool_manager_instance.h#L127)
-    num_instance: usize,
-    instance_index: usize,
-    next_page_id: AtomicUsize,
-    clock_replacer: ClockReplacer
+    modified: bool,
+    /// LSN of the last mutation applied to this page; `sync()` must fsync
+    /// the log up to this LSN before the page itself is allowed to flush.
+    page_lsn: u64,
 }
 
 pub trait PoolManager {
@@ -32,20 +28,174 @@ pub trait PoolManager {
     fn flush_all_page(&mut self);
 }
 
+/// Front-end owning `num_instance` independent [`BufferPoolInstance`]s, each
+/// with its own queue, clock replacer, and lock. Every operation is routed
+/// to `inner[page_id % num_instance]`. The `PoolManager` methods take
+/// `&mut self`, so calls on one `BufferPoolManager` are still serialized by
+/// whoever holds it exclusively; the per-instance locks do not let two
+/// callers run at the same time.
+pub struct BufferPoolManager {
+    instances: Vec<Mutex<BufferPoolInstance>>,
+    num_instance: usize,
+    /// Round-robins which instance mints the next page, since `new_page`
+    /// has no `page_id` yet to route by.
+    next_instance: AtomicUsize,
+}
+
+impl BufferPoolManager {
+    pub fn new(pool_size: usize, disk_manager: Arc<dyn PageDevice>) -> Result<Self> {
+        Self::with_instances(pool_size, disk_manager, 1, None)
+    }
+
+    /// Same as [`Self::new`], but pages are also replicated to cluster
+    /// peers through `replicator` before `new_page`/`flush_page` return; a
+    /// failed replication is logged and does not fail the call.
+    pub fn new_with_replicator(
+        pool_size: usize,
+        disk_manager: Arc<dyn PageDevice>,
+        replicator: Arc<Replicator>,
+    ) -> Result<Self> {
+        Self::with_instances(pool_size, disk_manager, 1, Some(replicator))
+    }
+
+    /// Partition `pool_size` pages across `num_instance` independent
+    /// instances, configurable from the CLI/config so the pool scales with
+    /// the number of cores actually contending for it.
+    pub fn with_instances(
+        pool_size: usize,
+        disk_manager: Arc<dyn PageDevice>,
+        num_instance: usize,
+        replicator: Option<Arc<Replicator>>,
+    ) -> Result<Self> {
+        let num_instance = num_instance.max(1);
+        // Spread the remainder across the first `pool_size % num_instance`
+        // instances instead of truncating it, so total cached capacity
+        // matches `pool_size` whenever there are enough pages configured to
+        // go around; with fewer pages than instances, every instance still
+        // gets its required one-page floor, so capacity exceeds `pool_size`.
+        let base_pool_size = pool_size / num_instance;
+        let remainder = pool_size % num_instance;
+
+        let mut instances = Vec::with_capacity(num_instance);
+        for instance_index in 0..num_instance {
+            let instance_pool_size = if base_pool_size == 0 {
+                1
+            } else if instance_index < remainder {
+                base_pool_size + 1
+            } else {
+                base_pool_size
+            };
+            instances.push(Mutex::new(BufferPoolInstance::new(
+                instance_pool_size,
+                Arc::clone(&disk_manager),
+                num_instance,
+                instance_index,
+                replicator.clone(),
+            )?));
+        }
+
+        Ok(Self { instances, num_instance, next_instance: AtomicUsize::new(0) })
+    }
+
+    fn instance_for(&self, page_id: usize) -> &Mutex<BufferPoolInstance> {
+        &self.instances[page_id % self.num_instance]
+    }
+}
+
+impl PoolManager for BufferPoolManager {
+    fn fetch_page(&mut self, page_id: usize) -> Option<Arc<Mutex<BufferPoolCache>>> {
+        self.instance_for(page_id).lock().unwrap().fetch_page(page_id)
+    }
+
+    fn un_pin(&mut self, page_id: usize) {
+        self.instance_for(page_id).lock().unwrap().un_pin(page_id)
+    }
+
+    fn flush_page(&mut self, page_id: usize) {
+        self.instance_for(page_id).lock().unwrap().flush_page(page_id)
+    }
+
+    fn new_page(&mut self) -> Arc<Mutex<BufferPoolCache>> {
+        let instance_index = self.next_instance.fetch_add(1, Ordering::Relaxed) % self.num_instance;
+        self.instances[instance_index].lock().unwrap().new_page()
+    }
+
+    fn delete_page(&mut self, page_id: usize) {
+        self.instance_for(page_id).lock().unwrap().delete_page(page_id)
+    }
+
+    fn flush_all_page(&mut self) {
+        for instance in &self.instances {
+            instance.lock().unwrap().flush_all_page();
+        }
+    }
+}
+
+struct BufferPoolInstance {
+    queue: VecDeque<(usize, Arc<Mutex<BufferPoolCache>>)>,
+    disk_manager: Arc<dyn PageDevice>,
+    pool_size: usize,
+    /// [summary](https://github.com/cmu-db/bustub/blob/6f4e74f6eb5f56a13bac9b9b9bbc3b2a80b41258/src/include/buffer/buffer_pool_manager_instance.h#L127)
+    num_instance: usize,
+    instance_index: usize,
+    next_page_id: AtomicUsize,
+    clock_replacer: ClockReplacer,
+    /// When set, `new_page`/`flush_page` replicate to peers before
+    /// returning. A replication that misses its quorum is only logged; the
+    /// call itself still completes normally.
+    replicator: Option<Arc<Replicator>>,
+}
+
 /// 缓冲的可以参考操作系统教学中的block cache实现
 /// 各个函数的定义：
 /// https://github.com/cmu-db/bustub/blob/master/src/buffer/buffer_pool_manager_instance.cpp
 /// https://github.com/cmu-db/bustub/blob/master/src/include/buffer/buffer_pool_manager_instance.h
-impl BufferPoolManager {
-    pub fn new(pool_size: usize, disk_manager: Arc<dyn DiskManager>) -> Self {
-        Self {
+impl BufferPoolInstance {
+    fn new(
+        pool_size: usize,
+        disk_manager: Arc<dyn PageDevice>,
+        num_instance: usize,
+        instance_index: usize,
+        replicator: Option<Arc<Replicator>>,
+    ) -> Result<Self> {
+        // Seed past whatever recovery just restored, so the first
+        // `new_page()` after a restart can't reallocate a page id that
+        // still holds recovered data. Clamped to the table-page range so an
+        // overflow chunk already written past `OVERFLOW_PAGE_BASE` doesn't
+        // balloon `allocated` and turn this into a multi-billion-iteration
+        // loop.
+        let allocated = table_page_bound(disk_manager.allocated_pages()?);
+        let next_page_id = Self::first_owned_page_id(allocated, num_instance, instance_index);
+
+        Ok(Self {
             queue: VecDeque::new(),
             pool_size,
             disk_manager,
-            num_instance : 1,
-            instance_index: 0,
-            next_page_id: AtomicUsize::new(0),
-            clock_replacer: ClockReplacer::new(pool_size)
+            num_instance,
+            instance_index,
+            next_page_id: AtomicUsize::new(next_page_id),
+            clock_replacer: ClockReplacer::new(pool_size),
+            replicator,
+        })
+    }
+
+    /// The smallest page id `>= floor` congruent to `instance_index` modulo
+    /// `num_instance`, i.e. the first id in this instance's stride that
+    /// isn't already on disk.
+    fn first_owned_page_id(floor: u32, num_instance: usize, instance_index: usize) -> usize {
+        let mut candidate = instance_index;
+        while (candidate as u32) < floor {
+            candidate += num_instance;
+        }
+        candidate
+    }
+
+    fn replicate(&self, page_id: usize, cache: &Arc<Mutex<BufferPoolCache>>) {
+        if let Some(replicator) = &self.replicator {
+            let data = cache.lock().unwrap().cache.to_vec();
+            if let Err(err) = replicator.replicate(page_id as u32, &data, ReplicationClass::Sharded) {
+                log::error!("replication failed for page {}: {}", page_id, err);
+            }
         }
     }
 
@@ -70,6 +220,7 @@ impl BufferPoolManager {
                 if let Some((idx, _)) = self.queue.iter().enumerate().find(|(_, pair)| pair.0 == remove_id) {
                     let (_, cache) = self.queue.remove(idx).unwrap();
                     cache.lock().unwrap().sync();
+                    METRICS.page_uncached();
                 } else {
                     panic!("Data is out of sync");
                 }
@@ -77,6 +228,7 @@ impl BufferPoolManager {
                 if let Some((idx, _)) = self.queue.iter().enumerate().find(|(_, pair)| Arc::strong_count(&pair.1) == 1) {
                     let (_, cache) = self.queue.remove(idx).unwrap();
                     cache.lock().unwrap().sync();
+                    METRICS.page_uncached();
                 } else {
                     panic!("Run out of Cache");
                 }
@@ -85,19 +237,23 @@ impl BufferPoolManager {
     }
 }
 
-impl PoolManager for BufferPoolManager {
+impl PoolManager for BufferPoolInstance {
 
     fn fetch_page(&mut self, page_id: usize) -> Option<Arc<Mutex<BufferPoolCache>>> {
         self.clock_replacer.pin(page_id);
 
         if let Some((_, cache)) = self.queue.iter().find(|(id, _)| page_id.eq(id)) {
+            METRICS.record_buffer_hit();
             let page_cache = Arc::clone(&cache);
             return Some(page_cache);
         }
+
+        METRICS.record_buffer_miss();
         if let Some(cache) = BufferPoolCache::read(page_id, Arc::clone(&self.disk_manager)) {
             self.check_queue();
             let page_cache = Arc::new(Mutex::new(cache));
             self.queue.push_back((page_id, Arc::clone(&page_cache)));
+            METRICS.page_cached();
             return Some(page_cache);
         }
 
@@ -111,6 +267,7 @@ impl PoolManager for BufferPoolManager {
     fn flush_page(&mut self, page_id: usize) {
         if let Some((_, cache)) = self.queue.iter().find(|(pid, _)| page_id.eq(pid)) {
             cache.lock().unwrap().sync();
+            self.replicate(page_id, cache);
         }
     }
 
@@ -122,6 +279,8 @@ impl PoolManager for BufferPoolManager {
         let cache = BufferPoolCache::create(page_id, disk_manager);
         let page_cache = Arc::new(Mutex::new(cache));
         self.queue.push_back((page_id, Arc::clone(&page_cache)));
+        METRICS.page_cached();
+        self.replicate(page_id, &page_cache);
         page_cache
     }
 
@@ -130,33 +289,41 @@ impl PoolManager for BufferPoolManager {
             let (_, cache) = self.queue.remove(idx).unwrap();
             self.clock_replacer.un_pin(page_id);
             cache.lock().unwrap().sync();
+            METRICS.page_uncached();
         }
     }
 
     fn flush_all_page(&mut self) {
+        let mut durable_lsn = 0;
         for (_, cache) in &self.queue {
-            cache.lock().unwrap().sync();
+            let mut cache = cache.lock().unwrap();
+            cache.sync();
+            durable_lsn = durable_lsn.max(cache.page_lsn);
         }
+        // A checkpoint after a full flush lets recovery start from here
+        // instead of rescanning the whole log.
+        let _ = self.disk_manager.wal().checkpoint(durable_lsn);
     }
 
 }
 
 impl BufferPoolCache {
 
-    pub fn create(page_id: usize, disk_manager: Arc<dyn DiskManager>) -> Self {
+    pub fn create(page_id: usize, disk_manager: Arc<dyn PageDevice>) -> Self {
         let page_data = [0u8; PAGE_SIZE];
-        disk_manager.write_page(page_id, &page_data);
+        disk_manager.write_page(page_id as u32, &page_data);
         Self {
             cache: page_data,
             disk_manager,
             page_id,
-            modified: false
+            modified: false,
+            page_lsn: 0,
         }
     }
 
-    pub fn read(page_id: usize, disk_manager: Arc<dyn DiskManager>) -> Option<Self> {
+    pub fn read(page_id: usize, disk_manager: Arc<dyn PageDevice>) -> Option<Self> {
         let mut page_data = [0u8; PAGE_SIZE];
-        if let Ok(state) = disk_manager.read_page(page_id, &mut page_data) {
+        if let Ok(state) = disk_manager.read_page(page_id as u32, &mut page_data) {
             return match state {
                 0 => {
                     None
@@ -166,7 +333,8 @@ impl BufferPoolCache {
                         cache: page_data,
                         disk_manager,
                         page_id,
-                        modified: false
+                        modified: false,
+                        page_lsn: 0,
                     })
                 }
             }
@@ -174,9 +342,35 @@ impl BufferPoolCache {
         None
     }
 
-    pub fn sync(&self) {
+    /// Mutate `len` bytes at `offset`, logging the after-image first so the
+    /// mutation survives a crash before it's ever reflected on disk.
+    pub fn write_at(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        let wal = self.disk_manager.wal();
+        let lsn = wal.next_lsn();
+        wal.append(&LogRecord {
+            lsn,
+            page_id: self.page_id as u32,
+            offset: offset as u32,
+            after_image: data.to_vec(),
+        })?;
+
+        self.cache[offset..offset + data.len()].copy_from_slice(data);
+        self.page_lsn = lsn;
+        if !self.modified {
+            self.modified = true;
+            METRICS.page_dirtied();
+        }
+        Ok(())
+    }
+
+    pub fn sync(&mut self) {
         if self.modified {
-            self.disk_manager.write_page(self.page_id, &self.cache);
+            // Write-ahead rule: the log up to this page's LSN must be
+            // durable before the page itself is allowed to hit disk.
+            let _ = self.disk_manager.wal().sync_through(self.page_lsn);
+            self.disk_manager.write_page(self.page_id as u32, &self.cache);
+            self.modified = false;
+            METRICS.page_cleaned();
         }
     }
 
@@ -198,8 +392,8 @@ mod test {
     #[test]
     fn test() -> Result<()> {
         let dir = tempdir::TempDir::new("mydb")?;
-        let disk_manager: Arc<dyn DiskManager> = Arc::new(PageDevice::open(dir.as_ref())?);
-        let mut buffer_pool_manager:Box<dyn PoolManager> = Box::new(BufferPoolManager::new(4, disk_manager));
+        let disk_manager: Arc<dyn PageDevice> = Arc::new(DiskManager::new(dir.as_ref())?);
+        let mut buffer_pool_manager:Box<dyn PoolManager> = Box::new(BufferPoolManager::new(4, disk_manager)?);
 
         let _header_page = buffer_pool_manager.new_page();
         {
@@ -221,4 +415,36 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn with_instances_routes_pages_across_instances_and_flushes_all_of_them() -> Result<()> {
+        let dir = tempdir::TempDir::new("mydb")?;
+        let disk_manager: Arc<dyn PageDevice> = Arc::new(DiskManager::new(dir.as_ref())?);
+        let num_instance = 3;
+        let mut pool: Box<dyn PoolManager> =
+            Box::new(BufferPoolManager::with_instances(10, disk_manager, num_instance, None)?);
+
+        // Minting `num_instance` pages round-robins across every instance,
+        // so each page id lands in a distinct residue class mod num_instance.
+        let mut page_ids = Vec::new();
+        for _ in 0..num_instance {
+            let page = pool.new_page();
+            let page_id = page.lock().unwrap().page_id;
+            page_ids.push(page_id);
+            pool.un_pin(page_id);
+        }
+        let mut residues: Vec<usize> = page_ids.iter().map(|id| id % num_instance).collect();
+        residues.sort();
+        assert_eq!(residues, (0..num_instance).collect::<Vec<_>>());
+
+        // Each instance caches and evicts independently, so every page
+        // minted above is still fetchable through its own instance.
+        for &page_id in &page_ids {
+            assert!(pool.fetch_page(page_id).is_some());
+        }
+
+        // flush_all_page must reach every instance, not just the first.
+        pool.flush_all_page();
+        Ok(())
+    }
+
 }
\ No newline at end of file
@@ -1,5 +1,7 @@
 use std::collections::VecDeque;
 
+use crate::storage::metrics::METRICS;
+
 #[derive(Debug)]
 pub struct ClockReplacer {
     queue: VecDeque<(bool, usize)>,
@@ -44,6 +46,7 @@ impl ClockReplacer {
         for q in &mut self.queue {
             if q.0 {
                 q.0 = false;
+                METRICS.record_eviction();
                 return Some(q.1);
             }
         }
@@ -0,0 +1,137 @@
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+use serde_derive::{Deserialize, Serialize};
+
+/// Process-wide counters instrumenting the buffer pool and disk I/O, so
+/// `Request::Status` and the admin Prometheus endpoint read the same
+/// numbers.
+pub struct Metrics {
+    buffer_hits: AtomicU64,
+    buffer_misses: AtomicU64,
+    page_reads: AtomicU64,
+    page_writes: AtomicU64,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    evictions: AtomicU64,
+    cached_pages: AtomicI64,
+    dirty_pages: AtomicI64,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            buffer_hits: AtomicU64::new(0),
+            buffer_misses: AtomicU64::new(0),
+            page_reads: AtomicU64::new(0),
+            page_writes: AtomicU64::new(0),
+            bytes_read: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            cached_pages: AtomicI64::new(0),
+            dirty_pages: AtomicI64::new(0),
+        }
+    }
+
+    pub fn record_buffer_hit(&self) {
+        self.buffer_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_buffer_miss(&self) {
+        self.buffer_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_page_read(&self, bytes: usize) {
+        self.page_reads.fetch_add(1, Ordering::Relaxed);
+        self.bytes_read.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_page_write(&self, bytes: usize) {
+        self.page_writes.fetch_add(1, Ordering::Relaxed);
+        self.bytes_written.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_eviction(&self) {
+        self.evictions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn page_cached(&self) {
+        self.cached_pages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn page_uncached(&self) {
+        self.cached_pages.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn page_dirtied(&self) {
+        self.dirty_pages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn page_cleaned(&self) {
+        self.dirty_pages.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn hit_ratio(&self) -> f64 {
+        let hits = self.buffer_hits.load(Ordering::Relaxed) as f64;
+        let misses = self.buffer_misses.load(Ordering::Relaxed) as f64;
+        if hits + misses == 0.0 {
+            0.0
+        } else {
+            hits / (hits + misses)
+        }
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            hit_ratio: self.hit_ratio(),
+            total_pages_cached: self.cached_pages.load(Ordering::Relaxed).max(0) as u64,
+            dirty_page_count: self.dirty_pages.load(Ordering::Relaxed).max(0) as u64,
+            evictions: self.evictions.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Render all counters in Prometheus text exposition format.
+    pub fn render_prometheus(&self) -> String {
+        let snapshot = self.snapshot();
+        format!(
+            "# HELP relationaldb_buffer_pool_hit_ratio Buffer pool hit ratio.\n\
+             # TYPE relationaldb_buffer_pool_hit_ratio gauge\n\
+             relationaldb_buffer_pool_hit_ratio {hit_ratio}\n\
+             # HELP relationaldb_buffer_pool_cached_pages Pages currently cached.\n\
+             # TYPE relationaldb_buffer_pool_cached_pages gauge\n\
+             relationaldb_buffer_pool_cached_pages {cached}\n\
+             # HELP relationaldb_buffer_pool_dirty_pages Cached pages with unflushed writes.\n\
+             # TYPE relationaldb_buffer_pool_dirty_pages gauge\n\
+             relationaldb_buffer_pool_dirty_pages {dirty}\n\
+             # HELP relationaldb_buffer_pool_evictions_total Pages evicted by the clock replacer.\n\
+             # TYPE relationaldb_buffer_pool_evictions_total counter\n\
+             relationaldb_buffer_pool_evictions_total {evictions}\n\
+             # HELP relationaldb_disk_bytes_read_total Bytes read from disk.\n\
+             # TYPE relationaldb_disk_bytes_read_total counter\n\
+             relationaldb_disk_bytes_read_total {bytes_read}\n\
+             # HELP relationaldb_disk_bytes_written_total Bytes written to disk.\n\
+             # TYPE relationaldb_disk_bytes_written_total counter\n\
+             relationaldb_disk_bytes_written_total {bytes_written}\n",
+            hit_ratio = snapshot.hit_ratio,
+            cached = snapshot.total_pages_cached,
+            dirty = snapshot.dirty_page_count,
+            evictions = snapshot.evictions,
+            bytes_read = snapshot.bytes_read,
+            bytes_written = snapshot.bytes_written,
+        )
+    }
+}
+
+/// A point-in-time read of [`Metrics`], returned from `Request::Status`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    pub hit_ratio: f64,
+    pub total_pages_cached: u64,
+    pub dirty_page_count: u64,
+    pub evictions: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+pub static METRICS: Metrics = Metrics::new();
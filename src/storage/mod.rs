@@ -1,7 +1,27 @@
 mod clock_replacer;
-mod disk_manager;
-mod buffer_pool_manager;
+pub(crate) mod disk_manager;
+pub(crate) mod buffer_pool_manager;
 mod page;
+pub mod wal;
+pub mod overflow;
+pub mod metrics;
 
 // page size 1MB
 pub const PAGE_SIZE: usize = 1024 * 4;
+
+/// First page id reserved for [`overflow::ChunkStore`]. Table pages are
+/// allocated upward from `0` by `BufferPoolInstance`, so this leaves that
+/// whole range free and keeps overflow chunks out of it without the two
+/// allocators needing to coordinate.
+pub(crate) const OVERFLOW_PAGE_BASE: u32 = u32::MAX / 2;
+
+/// Clamp a [`disk_manager::PageDevice::allocated_pages`] reading to the
+/// table-page range, for callers that scan or seed an allocator over *table*
+/// pages specifically. `allocated_pages()` is file-length-derived, so once an
+/// overflow chunk has been written out past [`OVERFLOW_PAGE_BASE`] it no
+/// longer means "how many table pages exist" — without this clamp a caller
+/// iterating `0..allocated_pages()` would walk the entire, mostly-empty
+/// overflow range too.
+pub(crate) fn table_page_bound(allocated: u32) -> u32 {
+    allocated.min(OVERFLOW_PAGE_BASE)
+}
@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::{BufReader, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crc32fast::Hasher;
+
+use crate::error::{Error, Result};
+use crate::storage::disk_manager::PageDevice;
+use crate::storage::{OVERFLOW_PAGE_BASE, PAGE_SIZE};
+
+/// 48-byte sliding window for the rolling fingerprint.
+const WINDOW: usize = 48;
+/// `fingerprint & MASK == 0` fires on average once every 64 KB; since
+/// `MAX_CHUNK` forces a boundary at 16 KB, most chunks end at that cap.
+const MASK: u64 = (1 << 16) - 1;
+const MIN_CHUNK: usize = PAGE_SIZE;
+const MAX_CHUNK: usize = 4 * PAGE_SIZE;
+/// Polynomial base for the Rabin-style rolling fingerprint.
+const BASE: u64 = 1_000_003;
+
+/// Split `data` on content-defined boundaries so inserting or deleting a
+/// few bytes only shifts the chunk(s) around the edit, not everything after
+/// it. Declares a boundary once at least `MIN_CHUNK` bytes have been seen
+/// and the rolling fingerprint of the trailing `WINDOW` bytes hits `MASK`,
+/// forcing one at `MAX_CHUNK` regardless so a single chunk can't run away.
+fn chunk_boundaries(data: &[u8]) -> Vec<&[u8]> {
+    if data.len() <= MIN_CHUNK {
+        return vec![data];
+    }
+
+    let mut window_base = 1u64;
+    for _ in 0..WINDOW.saturating_sub(1) {
+        window_base = window_base.wrapping_mul(BASE);
+    }
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut fingerprint: u64 = 0;
+
+    for i in 0..data.len() {
+        let len = i - start + 1;
+        fingerprint = fingerprint.wrapping_mul(BASE).wrapping_add(data[i] as u64);
+        if len > WINDOW {
+            let outgoing = data[i - WINDOW] as u64;
+            fingerprint = fingerprint.wrapping_sub(outgoing.wrapping_mul(window_base).wrapping_mul(BASE));
+        }
+
+        if len >= MIN_CHUNK && (len == MAX_CHUNK || fingerprint & MASK == 0) {
+            chunks.push(&data[start..=i]);
+            start = i + 1;
+            fingerprint = 0;
+        }
+    }
+    if start < data.len() {
+        chunks.push(&data[start..]);
+    }
+    chunks
+}
+
+pub type ChunkHash = [u8; 32];
+
+struct ChunkEntry {
+    /// First of the `ceil(len / PAGE_SIZE)` consecutive pages the chunk's
+    /// bytes are stored across.
+    page_id: u32,
+    len: usize,
+    refcount: u32,
+}
+
+const INDEX_FILE_NAME: &str = "overflow.idx";
+const INSERT_MAGIC: u8 = 0xE1;
+const REF_DELTA_MAGIC: u8 = 0xE2;
+
+/// One mutation to the hash -> page dedup index, appended to `overflow.idx`
+/// before it's applied in memory so a restart can replay the file and end up
+/// with the exact same index a prior run built — without this, `ChunkStore`
+/// forgets every hash it ever stored the moment the process exits.
+enum IndexRecord {
+    /// A brand-new chunk was written at `page_id`, starting at refcount 1.
+    Insert { hash: ChunkHash, page_id: u32, len: usize },
+    /// An existing chunk gained or lost a reference.
+    RefDelta { hash: ChunkHash, delta: i32 },
+}
+
+impl IndexRecord {
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(45);
+        match self {
+            IndexRecord::Insert { hash, page_id, len } => {
+                buf.push(INSERT_MAGIC);
+                buf.extend_from_slice(hash);
+                buf.extend_from_slice(&page_id.to_le_bytes());
+                buf.extend_from_slice(&(*len as u32).to_le_bytes());
+            }
+            IndexRecord::RefDelta { hash, delta } => {
+                buf.push(REF_DELTA_MAGIC);
+                buf.extend_from_slice(hash);
+                buf.extend_from_slice(&delta.to_le_bytes());
+            }
+        }
+
+        let mut hasher = Hasher::new();
+        hasher.update(&buf);
+        buf.extend_from_slice(&hasher.finalize().to_le_bytes());
+        buf
+    }
+
+    /// Read one record from `reader`. Returns `Ok(None)` on a clean EOF and
+    /// on a torn/corrupt tail alike — either way replay should just stop,
+    /// the same convention [`crate::storage::wal::LogRecord::decode`] uses.
+    fn decode(reader: &mut impl Read) -> Result<Option<IndexRecord>> {
+        let mut magic = [0u8; 1];
+        if reader.read_exact(&mut magic).is_err() {
+            return Ok(None);
+        }
+        let mut hash = [0u8; 32];
+        if reader.read_exact(&mut hash).is_err() {
+            return Ok(None);
+        }
+
+        let (record, verify) = match magic[0] {
+            INSERT_MAGIC => {
+                let mut page_id_buf = [0u8; 4];
+                let mut len_buf = [0u8; 4];
+                if reader.read_exact(&mut page_id_buf).is_err() || reader.read_exact(&mut len_buf).is_err() {
+                    return Ok(None);
+                }
+                let mut verify = vec![magic[0]];
+                verify.extend_from_slice(&hash);
+                verify.extend_from_slice(&page_id_buf);
+                verify.extend_from_slice(&len_buf);
+                let record = IndexRecord::Insert {
+                    hash,
+                    page_id: u32::from_le_bytes(page_id_buf),
+                    len: u32::from_le_bytes(len_buf) as usize,
+                };
+                (record, verify)
+            }
+            REF_DELTA_MAGIC => {
+                let mut delta_buf = [0u8; 4];
+                if reader.read_exact(&mut delta_buf).is_err() {
+                    return Ok(None);
+                }
+                let mut verify = vec![magic[0]];
+                verify.extend_from_slice(&hash);
+                verify.extend_from_slice(&delta_buf);
+                let record = IndexRecord::RefDelta { hash, delta: i32::from_le_bytes(delta_buf) };
+                (record, verify)
+            }
+            _ => return Ok(None),
+        };
+
+        let mut crc_buf = [0u8; 4];
+        if reader.read_exact(&mut crc_buf).is_err() {
+            return Ok(None);
+        }
+        let mut hasher = Hasher::new();
+        hasher.update(&verify);
+        if hasher.finalize().to_le_bytes() != crc_buf {
+            return Ok(None);
+        }
+
+        Ok(Some(record))
+    }
+}
+
+/// Replay `overflow.idx` in `dir` (if any) into a fresh index.
+fn recover_index(dir: &Path) -> Result<HashMap<ChunkHash, ChunkEntry>> {
+    let path = dir.join(INDEX_FILE_NAME);
+    let mut index = HashMap::new();
+    if !path.exists() {
+        return Ok(index);
+    }
+
+    let mut reader = BufReader::new(File::open(&path)?);
+    while let Some(record) = IndexRecord::decode(&mut reader)? {
+        match record {
+            IndexRecord::Insert { hash, page_id, len } => {
+                index.insert(hash, ChunkEntry { page_id, len, refcount: 1 });
+            }
+            IndexRecord::RefDelta { hash, delta } => {
+                if let Some(entry) = index.get_mut(&hash) {
+                    entry.refcount = (entry.refcount as i32 + delta).max(0) as u32;
+                    if entry.refcount == 0 {
+                        index.remove(&hash);
+                    }
+                }
+            }
+        }
+    }
+    Ok(index)
+}
+
+/// Content-addressed storage for values larger than a page. A value is
+/// chunked with [`chunk_boundaries`], each chunk is hashed to a content
+/// address, and identical chunks across different values share one copy.
+/// The hash -> page index is durable: every insert or refcount change is
+/// appended to `overflow.idx` before it's applied in memory, and replayed
+/// from there on restart, so a value stored by a prior run is still
+/// reachable (and still dedups against) after one.
+pub struct ChunkStore {
+    device: Arc<dyn PageDevice>,
+    index: Mutex<HashMap<ChunkHash, ChunkEntry>>,
+    next_page_id: AtomicU32,
+    index_log: Mutex<File>,
+}
+
+impl ChunkStore {
+    /// Seeds the chunk allocator past both the reserved [`OVERFLOW_PAGE_BASE`]
+    /// and whatever overflow pages a prior run already wrote, so it can
+    /// never hand out a page id a table page has claimed or a restart just
+    /// recovered, and replays `overflow.idx` in `dir` so the dedup index
+    /// survives the restart too.
+    pub fn new(device: Arc<dyn PageDevice>, dir: &Path) -> Result<Self> {
+        let next_page_id = device.allocated_pages()?.max(OVERFLOW_PAGE_BASE);
+        create_dir_all(dir)?;
+        let index = recover_index(dir)?;
+        let index_log = OpenOptions::new().append(true).create(true).open(dir.join(INDEX_FILE_NAME))?;
+        Ok(Self {
+            device,
+            index: Mutex::new(index),
+            next_page_id: AtomicU32::new(next_page_id),
+            index_log: Mutex::new(index_log),
+        })
+    }
+
+    /// Append `record` to `overflow.idx`, fsyncing it durable before
+    /// returning so a crash can never leave the in-memory index ahead of
+    /// what a replay would reconstruct.
+    fn append_index_record(&self, record: &IndexRecord) -> Result<()> {
+        let mut file = self.index_log.lock()?;
+        file.write_all(&record.encode())?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    fn pages_needed(len: usize) -> u32 {
+        ((len + PAGE_SIZE - 1) / PAGE_SIZE) as u32
+    }
+
+    fn allocate_pages(&self, len: usize) -> u32 {
+        let pages = Self::pages_needed(len).max(1);
+        self.next_page_id.fetch_add(pages, Ordering::SeqCst)
+    }
+
+    /// Writes each page of `chunk` through the WAL first, so a crash
+    /// mid-write leaves it recoverable by replay rather than a page of
+    /// garbage bytes that will never hash-match anything again.
+    fn write_chunk(&self, page_id: u32, chunk: &[u8]) -> Result<()> {
+        for (i, part) in chunk.chunks(PAGE_SIZE).enumerate() {
+            self.device.write_logged_page(page_id + i as u32, part)?;
+        }
+        Ok(())
+    }
+
+    fn read_chunk(&self, page_id: u32, len: usize) -> Result<Vec<u8>> {
+        let mut out = vec![0u8; len];
+        for (i, part) in out.chunks_mut(PAGE_SIZE).enumerate() {
+            self.device.read_page(page_id + i as u32, part)?;
+        }
+        Ok(out)
+    }
+
+    /// Store `data` as an ordered list of chunk hashes, writing through only
+    /// the chunks that aren't already in the index.
+    pub fn put_value(&self, data: &[u8]) -> Result<Vec<ChunkHash>> {
+        let mut hashes = Vec::with_capacity(data.len() / MIN_CHUNK + 1);
+        for chunk in chunk_boundaries(data) {
+            let hash: ChunkHash = blake3::hash(chunk).into();
+
+            let mut index = self.index.lock()?;
+            if let Some(entry) = index.get_mut(&hash) {
+                entry.refcount += 1;
+                drop(index);
+                self.append_index_record(&IndexRecord::RefDelta { hash, delta: 1 })?;
+            } else {
+                let page_id = self.allocate_pages(chunk.len());
+                drop(index);
+                self.write_chunk(page_id, chunk)?;
+                self.append_index_record(&IndexRecord::Insert { hash, page_id, len: chunk.len() })?;
+                index = self.index.lock()?;
+                index.insert(hash, ChunkEntry { page_id, len: chunk.len(), refcount: 1 });
+            }
+            hashes.push(hash);
+        }
+        Ok(hashes)
+    }
+
+    /// Reassemble a value from its ordered chunk hashes.
+    pub fn get_value(&self, hashes: &[ChunkHash]) -> Result<Vec<u8>> {
+        let index = self.index.lock()?;
+        let mut out = Vec::new();
+        for hash in hashes {
+            let entry = index
+                .get(hash)
+                .ok_or_else(|| Error::Internal("overflow chunk missing from index".to_string()))?;
+            out.extend(self.read_chunk(entry.page_id, entry.len)?);
+        }
+        Ok(out)
+    }
+
+    /// Drop one reference to each chunk in `hashes`; a chunk is only
+    /// forgotten once no value references it anymore.
+    pub fn remove_value(&self, hashes: &[ChunkHash]) -> Result<()> {
+        for hash in hashes {
+            self.append_index_record(&IndexRecord::RefDelta { hash: *hash, delta: -1 })?;
+            let mut index = self.index.lock()?;
+            if let Some(entry) = index.get_mut(hash) {
+                entry.refcount -= 1;
+                if entry.refcount == 0 {
+                    index.remove(hash);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::{chunk_boundaries, ChunkStore};
+    use crate::error::Result;
+    use crate::storage::disk_manager::{DiskManager, PageDevice};
+    use crate::storage::{OVERFLOW_PAGE_BASE, PAGE_SIZE};
+
+    #[test]
+    fn test() {
+        let small = vec![7u8; PAGE_SIZE / 2];
+        assert_eq!(chunk_boundaries(&small), vec![small.as_slice()]);
+
+        let large = vec![3u8; 10 * PAGE_SIZE];
+        let chunks = chunk_boundaries(&large);
+        let total: usize = chunks.iter().map(|c| c.len()).sum();
+        assert_eq!(total, large.len());
+        for chunk in &chunks[..chunks.len() - 1] {
+            assert!(chunk.len() >= PAGE_SIZE);
+            assert!(chunk.len() <= 4 * PAGE_SIZE);
+        }
+    }
+
+    #[test]
+    fn store_and_reassemble_a_value_stays_clear_of_table_pages() -> Result<()> {
+        let dir = tempdir::TempDir::new("mydb")?;
+        let device: Arc<dyn PageDevice> = Arc::new(DiskManager::new(dir.as_ref())?);
+        let store = ChunkStore::new(Arc::clone(&device), dir.as_ref())?;
+
+        let value = vec![9u8; 3 * PAGE_SIZE];
+        let hashes = store.put_value(&value)?;
+        assert_eq!(store.get_value(&hashes)?, value);
+
+        // A restart's allocator must never hand out a page id below the
+        // reserved base, however many table pages the run before it wrote.
+        let reopened = ChunkStore::new(Arc::clone(&device), dir.as_ref())?;
+        assert!(reopened.allocate_pages(1) >= OVERFLOW_PAGE_BASE);
+
+        // And the dedup index itself must survive the restart: a value
+        // stored by the prior run is still reassemblable from its hashes.
+        assert_eq!(reopened.get_value(&hashes)?, value);
+        Ok(())
+    }
+
+    #[test]
+    fn remove_then_reopen_does_not_resurrect_a_fully_dereferenced_chunk() -> Result<()> {
+        let dir = tempdir::TempDir::new("mydb")?;
+        let device: Arc<dyn PageDevice> = Arc::new(DiskManager::new(dir.as_ref())?);
+        let store = ChunkStore::new(Arc::clone(&device), dir.as_ref())?;
+
+        let value = vec![5u8; PAGE_SIZE / 2];
+        let hashes = store.put_value(&value)?;
+        store.remove_value(&hashes)?;
+
+        let reopened = ChunkStore::new(device, dir.as_ref())?;
+        assert!(reopened.get_value(&hashes).is_err());
+        Ok(())
+    }
+}
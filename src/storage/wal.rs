@@ -0,0 +1,316 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crc32fast::Hasher;
+
+use crate::error::Result;
+use crate::storage::PAGE_SIZE;
+
+const WAL_FILE_NAME: &str = "mydb.wal";
+const RECORD_MAGIC: u8 = 0xAB;
+const CHECKPOINT_MAGIC: u8 = 0xCC;
+
+/// A redo record: `after_image` is the bytes that must be reapplied at
+/// `offset` within `page_id` to bring it up to `lsn`.
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub lsn: u64,
+    pub page_id: u32,
+    pub offset: u32,
+    pub after_image: Vec<u8>,
+}
+
+impl LogRecord {
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(21 + self.after_image.len());
+        buf.push(RECORD_MAGIC);
+        buf.extend_from_slice(&self.lsn.to_le_bytes());
+        buf.extend_from_slice(&self.page_id.to_le_bytes());
+        buf.extend_from_slice(&self.offset.to_le_bytes());
+        buf.extend_from_slice(&(self.after_image.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&self.after_image);
+
+        let mut hasher = Hasher::new();
+        hasher.update(&buf);
+        buf.extend_from_slice(&hasher.finalize().to_le_bytes());
+        buf
+    }
+
+    /// Read one record from `reader`. Returns `Ok(None)` on a clean EOF and
+    /// on a torn tail / bad CRC alike — either way replay should stop.
+    fn decode(reader: &mut impl Read) -> Result<Option<LogRecord>> {
+        let mut lsn_buf = [0u8; 8];
+        let mut page_id_buf = [0u8; 4];
+        let mut offset_buf = [0u8; 4];
+        let mut length_buf = [0u8; 4];
+
+        if reader.read_exact(&mut lsn_buf).is_err() {
+            return Ok(None);
+        }
+        if reader.read_exact(&mut page_id_buf).is_err()
+            || reader.read_exact(&mut offset_buf).is_err()
+            || reader.read_exact(&mut length_buf).is_err()
+        {
+            return Ok(None);
+        }
+        let length = u32::from_le_bytes(length_buf) as usize;
+        let mut after_image = vec![0u8; length];
+        if reader.read_exact(&mut after_image).is_err() {
+            return Ok(None);
+        }
+        let mut crc_buf = [0u8; 4];
+        if reader.read_exact(&mut crc_buf).is_err() {
+            return Ok(None);
+        }
+
+        let mut verify = Vec::with_capacity(17 + length);
+        verify.push(RECORD_MAGIC);
+        verify.extend_from_slice(&lsn_buf);
+        verify.extend_from_slice(&page_id_buf);
+        verify.extend_from_slice(&offset_buf);
+        verify.extend_from_slice(&length_buf);
+        verify.extend_from_slice(&after_image);
+        let mut hasher = Hasher::new();
+        hasher.update(&verify);
+        if hasher.finalize().to_le_bytes() != crc_buf {
+            return Ok(None);
+        }
+
+        Ok(Some(LogRecord {
+            lsn: u64::from_le_bytes(lsn_buf),
+            page_id: u32::from_le_bytes(page_id_buf),
+            offset: u32::from_le_bytes(offset_buf),
+            after_image,
+        }))
+    }
+}
+
+/// Append-only redo log for the buffer pool, with per-page LSNs.
+///
+/// `BufferPoolCache::sync` must fsync the log up to a page's `page_lsn`
+/// before flushing that page, so a crash can never leave a page on disk
+/// whose mutation isn't also recoverable from the log.
+pub struct Wal {
+    file: Mutex<File>,
+    next_lsn: AtomicU64,
+}
+
+impl Wal {
+    fn open_file(dir: &Path) -> Result<File> {
+        Ok(OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(dir.join(WAL_FILE_NAME))?)
+    }
+
+    /// Next LSN to hand to a mutation; monotonically increasing for the
+    /// lifetime of the log.
+    pub fn next_lsn(&self) -> u64 {
+        self.next_lsn.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Append `record` and fsync it durable before returning.
+    pub fn append(&self, record: &LogRecord) -> Result<()> {
+        let mut file = self.file.lock()?;
+        file.write_all(&record.encode())?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Fsync the log so every record up to and including `lsn` is durable.
+    /// The write-ahead rule: callers must do this before flushing the page
+    /// that `lsn` belongs to.
+    pub fn sync_through(&self, _lsn: u64) -> Result<()> {
+        let file = self.file.lock()?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Record the durable flush LSN so the next recovery can skip straight
+    /// past everything already known to be on disk. The recorded LSN is
+    /// raised to the last one already handed out, so recovery resumes
+    /// `next_lsn()` past every record in the log, not just the flushed ones.
+    pub fn checkpoint(&self, durable_lsn: u64) -> Result<()> {
+        let durable_lsn = durable_lsn.max(self.next_lsn.load(Ordering::SeqCst).saturating_sub(1));
+        let mut file = self.file.lock()?;
+        let mut buf = Vec::with_capacity(9);
+        buf.push(CHECKPOINT_MAGIC);
+        buf.extend_from_slice(&durable_lsn.to_le_bytes());
+        file.write_all(&buf)?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// Scan the log forward from the last checkpoint and reapply every record
+/// after it in log order, stopping at the first torn/corrupt record instead
+/// of erroring out (a crash mid-append leaves exactly one torn tail, never
+/// garbage in the middle of the log). Pages carry no on-disk LSN, so a
+/// record is not compared against the page's persisted state; in-order
+/// replay leaves each page with its latest logged bytes. Returns the
+/// recovered `Wal`, primed so the next `next_lsn()` continues after the
+/// highest LSN seen.
+pub fn recover(dir: &Path, db_file: &File) -> Result<Wal> {
+    let log_path = dir.join(WAL_FILE_NAME);
+    let mut max_lsn = 0u64;
+
+    if log_path.exists() {
+        let (start_offset, checkpoint_lsn) = last_checkpoint(&log_path)?;
+        max_lsn = max_lsn.max(checkpoint_lsn);
+
+        let mut file = File::open(&log_path)?;
+        file.seek(SeekFrom::Start(start_offset))?;
+        let mut reader = BufReader::new(file);
+        let mut page_lsn: std::collections::HashMap<u32, u64> = std::collections::HashMap::new();
+        let mut magic = [0u8; 1];
+
+        while reader.read_exact(&mut magic).is_ok() {
+            match magic[0] {
+                CHECKPOINT_MAGIC => {
+                    let mut lsn_buf = [0u8; 8];
+                    if reader.read_exact(&mut lsn_buf).is_err() {
+                        break;
+                    }
+                    max_lsn = max_lsn.max(u64::from_le_bytes(lsn_buf));
+                }
+                RECORD_MAGIC => match LogRecord::decode(&mut reader)? {
+                    Some(record) => {
+                        max_lsn = max_lsn.max(record.lsn);
+                        let persisted = *page_lsn.get(&record.page_id).unwrap_or(&0);
+                        if record.lsn > persisted {
+                            apply_record(db_file, &record)?;
+                            page_lsn.insert(record.page_id, record.lsn);
+                        }
+                    }
+                    None => break,
+                },
+                _ => break,
+            }
+        }
+    }
+
+    Ok(Wal {
+        file: Mutex::new(Wal::open_file(dir)?),
+        next_lsn: AtomicU64::new(max_lsn + 1),
+    })
+}
+
+/// Find the byte offset right after the last checkpoint record (0 if there
+/// isn't one) and the LSN it recorded, so `recover` can seek past every
+/// record the checkpoint already covers instead of reapplying it.
+fn last_checkpoint(log_path: &Path) -> Result<(u64, u64)> {
+    let mut reader = BufReader::new(File::open(log_path)?);
+    let mut offset = 0u64;
+    let mut lsn = 0u64;
+    let mut magic = [0u8; 1];
+
+    while reader.read_exact(&mut magic).is_ok() {
+        match magic[0] {
+            CHECKPOINT_MAGIC => {
+                let mut lsn_buf = [0u8; 8];
+                if reader.read_exact(&mut lsn_buf).is_err() {
+                    break;
+                }
+                lsn = u64::from_le_bytes(lsn_buf);
+                offset = reader.stream_position()?;
+            }
+            RECORD_MAGIC => match LogRecord::decode(&mut reader)? {
+                Some(_) => {}
+                None => break,
+            },
+            _ => break,
+        }
+    }
+    Ok((offset, lsn))
+}
+
+fn apply_record(db_file: &File, record: &LogRecord) -> Result<()> {
+    let mut page = vec![0u8; PAGE_SIZE];
+    let page_offset = record.page_id as u64 * PAGE_SIZE as u64;
+    let mut file = db_file.try_clone()?;
+
+    if file.metadata()?.len() > page_offset {
+        file.seek(SeekFrom::Start(page_offset))?;
+        let _ = file.read(&mut page);
+    }
+
+    let start = record.offset as usize;
+    let end = start + record.after_image.len();
+    page[start..end].copy_from_slice(&record.after_image);
+
+    file.seek(SeekFrom::Start(page_offset))?;
+    file.write_all(&page)?;
+    file.sync_data()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::Result;
+    use std::fs::OpenOptions;
+
+    #[test]
+    fn recovers_after_image_on_reopen() -> Result<()> {
+        let dir = tempdir::TempDir::new("mydb_wal")?;
+        let db_path = dir.as_ref().join("mydb.db");
+        let db_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&db_path)?;
+        db_file.set_len(PAGE_SIZE as u64)?;
+
+        {
+            let wal = recover(dir.as_ref(), &db_file)?;
+            let lsn = wal.next_lsn();
+            wal.append(&LogRecord {
+                lsn,
+                page_id: 0,
+                offset: 0,
+                after_image: b"hello".to_vec(),
+            })?;
+        }
+
+        let wal = recover(dir.as_ref(), &db_file)?;
+        assert!(wal.next_lsn() > 0);
+
+        let mut page = vec![0u8; PAGE_SIZE];
+        let mut file = db_file.try_clone()?;
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut page)?;
+        assert_eq!(&page[..5], b"hello");
+        Ok(())
+    }
+
+    #[test]
+    fn recovery_seeks_past_last_checkpoint() -> Result<()> {
+        let dir = tempdir::TempDir::new("mydb_wal")?;
+        let db_path = dir.as_ref().join("mydb.db");
+        let db_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&db_path)?;
+        db_file.set_len(PAGE_SIZE as u64)?;
+
+        {
+            let wal = recover(dir.as_ref(), &db_file)?;
+            let lsn = wal.next_lsn();
+            wal.append(&LogRecord { lsn, page_id: 0, offset: 0, after_image: b"before".to_vec() })?;
+            wal.checkpoint(lsn)?;
+        }
+
+        let (start_offset, checkpoint_lsn) = last_checkpoint(&dir.as_ref().join(WAL_FILE_NAME))?;
+        assert!(start_offset > 0, "checkpoint offset should be past the prior record");
+        assert_eq!(checkpoint_lsn, 0);
+
+        let wal = recover(dir.as_ref(), &db_file)?;
+        assert_eq!(wal.next_lsn(), 1);
+        Ok(())
+    }
+}